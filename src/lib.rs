@@ -1,18 +1,20 @@
 //! Functions for dealing with wildcards and simple actions over recusive structures.
 
+use std::future::Future;
 use std::path::Path;
 use std::path::PathBuf;
 
+use futures::stream::StreamExt;
 use regex::Regex;
 
 /// Possible results of the wildcard functions.
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub enum WildcardingError {
     /// Whatever was used for this, we can't understand if it is a file or a directory.
     UnknownFormat(PathBuf),
 
     /// The operation reached an error and couldn't complete.
-    OperationFailed(PathBuf, PathBuf),
+    OperationFailed(PathBuf, PathBuf, std::io::Error),
 
     /// The source is invalid.
     InvalidSource(PathBuf),
@@ -30,138 +32,566 @@ pub enum WildcardingError {
     NoParent(PathBuf),
 
     /// Can't read the directory.
-    ReadError(PathBuf),
+    ReadError(PathBuf, std::io::Error),
+
+    /// The mask couldn't be compiled into a valid pattern.
+    InvalidPattern(PathBuf),
+
+    /// Failed to remove a path.
+    RemoveFailed(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for WildcardingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WildcardingError::UnknownFormat(path) => {
+                write!(f, "{}: not clearly a file or a directory", path.display())
+            }
+            WildcardingError::OperationFailed(source, target, err) => {
+                write!(
+                    f,
+                    "failed to act on {} -> {}: {err}",
+                    source.display(),
+                    target.display()
+                )
+            }
+            WildcardingError::InvalidSource(path) => write!(f, "{}: invalid source", path.display()),
+            WildcardingError::InvalidTarget(path) => write!(f, "{}: invalid target", path.display()),
+            WildcardingError::FilenameFail(path) => {
+                write!(f, "{}: couldn't get the file name", path.display())
+            }
+            WildcardingError::InvalidPath(path) => {
+                write!(f, "{}: not a valid utf-8 path", path.display())
+            }
+            WildcardingError::NoParent(path) => write!(f, "{}: has no parent", path.display()),
+            WildcardingError::ReadError(path, err) => {
+                write!(f, "failed to read {}: {err}", path.display())
+            }
+            WildcardingError::InvalidPattern(path) => {
+                write!(f, "{}: not a valid mask", path.display())
+            }
+            WildcardingError::RemoveFailed(path, err) => {
+                write!(f, "failed to remove {}: {err}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for WildcardingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WildcardingError::OperationFailed(_, _, err) => Some(err),
+            WildcardingError::ReadError(_, err) => Some(err),
+            WildcardingError::RemoveFailed(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Translate a shell glob mask into an anchored regular expression.
+///
+/// Literal characters that are also regex metacharacters are escaped, while
+/// the glob tokens `*`, `**`, `?` and `[...]`/`[!...]` are translated to their
+/// regex equivalents (`**` crosses directory boundaries, `*` doesn't). The
+/// result is wrapped in `^...$` so a mask like `*.txt` can't match in the
+/// middle of an unrelated string.
+fn glob_to_regex(mask: &str) -> String {
+    let mut pattern = String::from("^");
+    let mut chars = mask.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => {
+                pattern.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    pattern.push('^');
+                }
+                for c in chars.by_ref() {
+                    pattern.push(c);
+                    if c == ']' {
+                        break;
+                    }
+                }
+            }
+            '.' | '+' | '(' | ')' | '{' | '}' | '^' | '$' | '\\' | '|' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            c => pattern.push(c),
+        }
+    }
+
+    pattern.push('$');
+    pattern
+}
+
+/// Compile [`Options::exclude`] into regexes using the same rules as a mask.
+fn compile_exclude(patterns: &[String]) -> Result<Vec<Regex>, WildcardingError> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(&glob_to_regex(pattern))
+                .map_err(|_| WildcardingError::InvalidPattern(PathBuf::from(pattern)))
+        })
+        .collect()
+}
+
+/// The number of files a mask operation will process at once when [`Options::concurrency`]
+/// isn't overridden.
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Options that tweak how the wildcard operations behave.
+///
+/// [`Options::default`] is non-recursive, copies atomically, preserves metadata and excludes
+/// nothing.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Walk into subdirectories instead of only acting on their direct children.
+    pub recursive: bool,
+
+    /// How many files a mask match (`*`, `?`, `**`, ...) is allowed to act on at once.
+    pub concurrency: usize,
+
+    /// Write copies to a temp file next to the target and rename it into place, so a failed or
+    /// interrupted copy never leaves a partial file behind. Only used by [`cp`]-like functions.
+    pub atomic: bool,
+
+    /// Carry permissions and timestamps over from the source file to the target. Used by
+    /// [`cp`]-like functions directly, and by [`mv`]-like functions when they have to fall back
+    /// to copy-then-remove across a filesystem boundary.
+    pub preserve: bool,
+
+    /// Glob patterns (compiled with the same rules as a mask, see [`glob_to_regex`]) that are
+    /// subtracted from what a mask would otherwise match. A matched entry is skipped if its file
+    /// name matches any of these, and a directory holding a skipped entry is left in place by
+    /// the recursive `rm`/`mv` pruning instead of being removed out from under it.
+    ///
+    /// This only takes explicit patterns; a `.gitignore` in the source directory is not read.
+    pub exclude: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            atomic: true,
+            preserve: true,
+            exclude: Vec::new(),
+        }
+    }
 }
 
 /// Copy a file (with or without a wildcard) to a target.
 pub async fn cp(source: &Path, target: &Path) -> Result<(), WildcardingError> {
+    cp_with_options(source, target, &Options::default()).await
+}
+
+/// Copy a file or directory tree (with or without a wildcard) to a target, walking into
+/// subdirectories.
+pub async fn cp_recursive(source: &Path, target: &Path) -> Result<(), WildcardingError> {
+    cp_with_options(
+        source,
+        target,
+        &Options {
+            recursive: true,
+            ..Options::default()
+        },
+    )
+    .await
+}
+
+/// Copy a file (with or without a wildcard) to a target, following `options`.
+pub async fn cp_with_options(
+    source: &Path,
+    target: &Path,
+    options: &Options,
+) -> Result<(), WildcardingError> {
     tracing::debug!(?source, ?target);
+    let atomic = options.atomic;
+    let preserve = options.preserve;
     // this is the magical closure that says what to do when the operator need to act on a file.
-    let closure = |source: &Path, target: &Path| {
-        std::fs::copy(source, target).map(|_| ()).map_err(move |_| {
-            tracing::debug!(?source, ?target, "copy");
-            WildcardingError::OperationFailed(source.to_path_buf(), target.to_path_buf())
-        })
+    let closure = move |source: PathBuf, target: PathBuf| async move {
+        tracing::debug!(?source, ?target, "copy");
+        if atomic {
+            atomic_copy(&source, &target, preserve).await
+        } else {
+            tokio::fs::copy(&source, &target).await.map_err(|err| {
+                WildcardingError::OperationFailed(source.clone(), target.clone(), err)
+            })?;
+            if preserve {
+                preserve_metadata(&source, &target).await?;
+            }
+            Ok(())
+        }
     };
 
     match (source.is_file(), source.is_dir()) {
         (true, true) => Err(WildcardingError::UnknownFormat(source.to_path_buf())),
         (true, false) => do_on_file(source, target, closure).await,
-        (false, true) => do_on_dir(source, target, closure).await,
-        (false, false) => do_on_mask(source, target, closure).await,
+        (false, true) => do_on_dir(source, target, closure, options).await,
+        (false, false) => do_on_mask(source, target, closure, options).await,
+    }
+}
+
+/// Copy `source` into `target` atomically: the bytes land in a temp file next to `target`
+/// first, and only a final `rename` makes them visible at `target`, so a crash or error midway
+/// never leaves a truncated or corrupt destination file.
+async fn atomic_copy(source: &Path, target: &Path, preserve: bool) -> Result<(), WildcardingError> {
+    let parent = target
+        .parent()
+        .ok_or_else(|| WildcardingError::NoParent(target.to_path_buf()))?;
+    let temp_name = format!(
+        ".{}.tmp{}",
+        target.file_name().and_then(|n| n.to_str()).unwrap_or("wildcards"),
+        std::process::id()
+    );
+    let temp_path = parent.join(temp_name);
+
+    if let Err(err) = copy_into(source, &temp_path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(err);
+    }
+
+    // apply metadata to the temp file before the rename, so the swap into `target` is still a
+    // single atomic step and never exposes a file with the wrong permissions or timestamps.
+    if preserve {
+        if let Err(err) = preserve_metadata(source, &temp_path).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(err);
+        }
+    }
+
+    match tokio::fs::rename(&temp_path, target).await {
+        Ok(()) => Ok(()),
+        // the temp file lives next to `target`, so this should never actually cross devices,
+        // but fall back to a plain copy instead of leaving the temp file stranded if it does.
+        Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {
+            let result = tokio::fs::copy(&temp_path, target).await.map(|_| ());
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            result.map_err(|err| {
+                WildcardingError::OperationFailed(source.to_path_buf(), target.to_path_buf(), err)
+            })
+        }
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            Err(WildcardingError::OperationFailed(
+                source.to_path_buf(),
+                target.to_path_buf(),
+                err,
+            ))
+        }
     }
 }
 
+/// Copy the bytes of `source` into `destination`, flushing them to disk before returning.
+async fn copy_into(source: &Path, destination: &Path) -> Result<(), WildcardingError> {
+    let to_failure = |err: std::io::Error| {
+        WildcardingError::OperationFailed(source.to_path_buf(), destination.to_path_buf(), err)
+    };
+
+    let mut input = tokio::fs::File::open(source).await.map_err(to_failure)?;
+    let mut output = tokio::fs::File::create(destination)
+        .await
+        .map_err(to_failure)?;
+
+    tokio::io::copy(&mut input, &mut output)
+        .await
+        .map_err(to_failure)?;
+    output.sync_all().await.map_err(to_failure)?;
+
+    Ok(())
+}
+
+/// Carry `source`'s permissions and access/modification timestamps over onto `target`.
+async fn preserve_metadata(source: &Path, target: &Path) -> Result<(), WildcardingError> {
+    let to_failure = |err: std::io::Error| {
+        WildcardingError::OperationFailed(source.to_path_buf(), target.to_path_buf(), err)
+    };
+
+    let metadata = tokio::fs::metadata(source).await.map_err(to_failure)?;
+    tokio::fs::set_permissions(target, metadata.permissions())
+        .await
+        .map_err(to_failure)?;
+
+    let accessed = filetime::FileTime::from_last_access_time(&metadata);
+    let modified = filetime::FileTime::from_last_modification_time(&metadata);
+    let target_for_times = target.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        filetime::set_file_times(&target_for_times, accessed, modified)
+    })
+    .await
+    .map_err(|err| to_failure(std::io::Error::other(err)))?
+    .map_err(to_failure)
+}
+
 /// Move a file (with or without a wildcard) to a target.
 pub async fn mv(source: &Path, target: &Path) -> Result<(), WildcardingError> {
+    mv_with_options(source, target, &Options::default()).await
+}
+
+/// Move a file or directory tree (with or without a wildcard) to a target, walking into
+/// subdirectories.
+pub async fn mv_recursive(source: &Path, target: &Path) -> Result<(), WildcardingError> {
+    mv_with_options(
+        source,
+        target,
+        &Options {
+            recursive: true,
+            ..Options::default()
+        },
+    )
+    .await
+}
+
+/// Move a file (with or without a wildcard) to a target, following `options`.
+pub async fn mv_with_options(
+    source: &Path,
+    target: &Path,
+    options: &Options,
+) -> Result<(), WildcardingError> {
     tracing::debug!(?source, ?target);
-    let closure = |source: &Path, target: &Path| {
+    let preserve = options.preserve;
+    let closure = move |source: PathBuf, target: PathBuf| async move {
         tracing::debug!(?source, ?target, "rename");
-        std::fs::rename(source, target)
-            .map(|_| ())
-            .map_err(move |_| {
-                WildcardingError::OperationFailed(source.to_path_buf(), target.to_path_buf())
-            })
+        match tokio::fs::rename(&source, &target).await {
+            Ok(()) => Ok(()),
+            // `rename` can't cross filesystems, so fall back to copying the file over (carrying
+            // its metadata along) and removing the original afterwards.
+            Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {
+                copy_into(&source, &target).await?;
+                if preserve {
+                    preserve_metadata(&source, &target).await?;
+                }
+                tokio::fs::remove_file(&source)
+                    .await
+                    .map_err(|err| WildcardingError::OperationFailed(source, target, err))
+            }
+            Err(err) => Err(WildcardingError::OperationFailed(source, target, err)),
+        }
     };
 
     match (source.is_file(), source.is_dir()) {
         (true, true) => Err(WildcardingError::UnknownFormat(source.to_path_buf())),
         (true, false) => do_on_file(source, target, closure).await,
-        (false, true) => do_on_dir(source, target, closure).await,
-        (false, false) => do_on_mask(source, target, closure).await,
+        (false, true) if options.recursive => {
+            do_on_dir(source, target, closure, options).await?;
+            // Every un-excluded file has landed at `target` by now; prune what's left of
+            // `source` the same way `rm_recursive` does, leaving any file `exclude` kept in
+            // place (and the directories holding it) untouched.
+            let exclude = compile_exclude(&options.exclude)?;
+            if remove_dir_recursive(source, &exclude).await? {
+                tokio::fs::remove_dir(source)
+                    .await
+                    .map_err(|err| WildcardingError::RemoveFailed(source.to_path_buf(), err))
+            } else {
+                Ok(())
+            }
+        }
+        (false, true) => do_on_dir(source, target, closure, options).await,
+        (false, false) => do_on_mask(source, target, closure, options).await,
     }
 }
 
 /// Remove a file (with or without a wildcard).
 pub async fn rm(source: &Path) -> Result<(), WildcardingError> {
+    rm_with_options(source, &Options::default()).await
+}
+
+/// Remove a file or directory tree (with or without a wildcard), walking into subdirectories
+/// and removing directories that end up empty.
+pub async fn rm_recursive(source: &Path) -> Result<(), WildcardingError> {
+    rm_with_options(
+        source,
+        &Options {
+            recursive: true,
+            ..Options::default()
+        },
+    )
+    .await
+}
+
+/// Remove a file (with or without a wildcard), following `options`.
+pub async fn rm_with_options(source: &Path, options: &Options) -> Result<(), WildcardingError> {
     // Quick note: `rm` abuses the functionality below by asking them to transverse the files like
     // cp and mv do, but uses a target that we never touch again.
     tracing::debug!(?source);
-    let closure = |source: &Path, _target: &Path| {
+    let closure = |source: PathBuf, _target: PathBuf| async move {
         tracing::debug!(?source, "delete");
-        std::fs::remove_file(source)
+        tokio::fs::remove_file(&source)
+            .await
             .map(|_| ())
-            .map_err(move |_| WildcardingError::InvalidSource(source.to_path_buf()))
+            .map_err(move |err| WildcardingError::RemoveFailed(source, err))
     };
 
     let target = std::env::temp_dir(); // we will ignore the target, anyway.
     match (source.is_file(), source.is_dir()) {
         (true, true) => Err(WildcardingError::UnknownFormat(source.to_path_buf())),
         (true, false) => do_on_file(source, &target, closure).await,
-        (false, true) => do_on_dir(source, &target, closure).await,
-        (false, false) => do_on_mask(source, &target, closure).await,
+        (false, true) if options.recursive => {
+            let exclude = compile_exclude(&options.exclude)?;
+            if remove_dir_recursive(source, &exclude).await? {
+                tokio::fs::remove_dir(source)
+                    .await
+                    .map_err(|err| WildcardingError::RemoveFailed(source.to_path_buf(), err))
+            } else {
+                Ok(())
+            }
+        }
+        (false, true) => do_on_dir(source, &target, closure, options).await,
+        (false, false) => do_on_mask(source, &target, closure, options).await,
     }
 }
 
-/// Act on a file.
-async fn do_on_file<T>(source: &Path, target: &Path, op: T) -> Result<(), WildcardingError>
-where
-    T: Fn(&Path, &Path) -> Result<(), WildcardingError>
-        + Send
-        + std::marker::Sync
-        + std::marker::Copy,
-{
+/// Work out the final target path for a single file, joining the file's name under `target`
+/// when `target` is a directory.
+fn resolve_target(source: &Path, target: &Path) -> Result<PathBuf, WildcardingError> {
     if target.is_dir() {
         let filename = source
             .file_name()
             .ok_or_else(|| WildcardingError::FilenameFail(source.to_path_buf()))?;
-        let new_target = target.join(&filename);
-        tracing::debug!(?source, ?new_target);
-        op(source, &new_target)
+        Ok(target.join(filename))
     } else {
-        op(source, target)
+        Ok(target.to_path_buf())
     }
 }
 
+/// Act on a file.
+async fn do_on_file<T, Fut>(source: &Path, target: &Path, op: T) -> Result<(), WildcardingError>
+where
+    T: Fn(PathBuf, PathBuf) -> Fut + Send + std::marker::Sync + std::marker::Copy,
+    Fut: Future<Output = Result<(), WildcardingError>> + Send,
+{
+    let new_target = resolve_target(source, target)?;
+    tracing::debug!(?source, ?new_target);
+    op(source.to_path_buf(), new_target).await
+}
+
 /// Act on a directory.
-async fn do_on_dir<T>(source: &Path, target: &Path, op: T) -> Result<(), WildcardingError>
+async fn do_on_dir<T, Fut>(
+    source: &Path,
+    target: &Path,
+    op: T,
+    options: &Options,
+) -> Result<(), WildcardingError>
 where
-    T: Fn(&Path, &Path) -> Result<(), WildcardingError>
-        + Send
-        + std::marker::Sync
-        + std::marker::Copy,
+    T: Fn(PathBuf, PathBuf) -> Fut + Send + std::marker::Sync + std::marker::Copy,
+    Fut: Future<Output = Result<(), WildcardingError>> + Send,
 {
     if !target.is_dir() {
         Err(WildcardingError::InvalidTarget(target.to_path_buf()))
+    } else if options.recursive {
+        let exclude = compile_exclude(&options.exclude)?;
+        do_on_dir_recursive(source, source, target, op, &exclude).await
     } else {
-        do_on_mask(&source.join("*"), target, op).await
+        do_on_mask(&source.join("*"), target, op, options).await
     }
 }
 
 /// Act on files with a certain mask.
-async fn do_on_mask<T>(source: &Path, target: &Path, op: T) -> Result<(), WildcardingError>
+///
+/// Matching entries are processed concurrently, bounded by [`Options::concurrency`], instead of
+/// one at a time, so large wildcard operations overlap their I/O.
+async fn do_on_mask<T, Fut>(
+    source: &Path,
+    target: &Path,
+    op: T,
+    options: &Options,
+) -> Result<(), WildcardingError>
 where
-    T: Fn(&Path, &Path) -> Result<(), WildcardingError>
-        + Send
-        + std::marker::Sync
-        + std::marker::Copy,
+    T: Fn(PathBuf, PathBuf) -> Fut + Send + std::marker::Sync + std::marker::Copy,
+    Fut: Future<Output = Result<(), WildcardingError>> + Send,
 {
     if let Some(name) = source.file_name() {
         let as_str = name
             .to_str()
             .ok_or_else(|| WildcardingError::InvalidPath(source.to_path_buf()))?;
-        if as_str.contains("*") {
+        if as_str.contains('*') || as_str.contains('?') || as_str.contains('[') {
             let source = source
                 .parent()
                 .ok_or_else(|| WildcardingError::NoParent(source.to_path_buf()))?;
-            let re = Regex::new(&as_str.replace("*", ".*")).unwrap();
-
-            let mut reader = tokio::fs::read_dir(&source)
-                .await
-                .map_err(|_| WildcardingError::ReadError(source.to_path_buf()))?;
-            while let Ok(Some(entry)) = reader.next_entry().await {
-                let entry = entry.path();
-                if entry.is_file() {
-                    if let Some(name) = entry.file_name() {
-                        let as_str = name
-                            .to_str()
-                            .ok_or_else(|| WildcardingError::InvalidPath(entry.to_path_buf()))?;
-                        if re.is_match(as_str) {
-                            do_on_file(&source.join(name), target, op).await?;
+            let re = Regex::new(&glob_to_regex(as_str))
+                .map_err(|_| WildcardingError::InvalidPattern(source.join(as_str)))?;
+            let exclude = compile_exclude(&options.exclude)?;
+
+            // A `**` token, or an explicit request for recursion, matches across directory
+            // boundaries instead of only the mask's immediate directory. Matches carry their
+            // target path already resolved, since a recursive match has to reproduce its
+            // relative path under `target` rather than flatten to `target`'s direct child.
+            let matches: Vec<(PathBuf, PathBuf)> = if options.recursive || as_str.contains("**") {
+                let files = list_files_recursive(source).await?;
+                let mut matches = Vec::new();
+                for file in files {
+                    let relative = file.strip_prefix(source).unwrap_or(&file);
+                    let relative_str = relative
+                        .to_str()
+                        .ok_or_else(|| WildcardingError::InvalidPath(file.to_path_buf()))?;
+                    let name = file
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .ok_or_else(|| WildcardingError::InvalidPath(file.to_path_buf()))?;
+                    // `*` and `?` never match `/`, so without a `**` the mask is matched against
+                    // the file name, letting an explicit recursion reach nested files.
+                    let subject = if as_str.contains("**") {
+                        relative_str
+                    } else {
+                        name
+                    };
+                    if re.is_match(subject) && !exclude.iter().any(|re| re.is_match(name)) {
+                        let new_target = target.join(relative);
+                        matches.push((file, new_target));
+                    }
+                }
+                matches
+            } else {
+                let mut matches = Vec::new();
+                let mut reader = tokio::fs::read_dir(&source)
+                    .await
+                    .map_err(|err| WildcardingError::ReadError(source.to_path_buf(), err))?;
+                while let Ok(Some(entry)) = reader.next_entry().await {
+                    let entry = entry.path();
+                    if entry.is_file() {
+                        if let Some(name) = entry.file_name() {
+                            let as_str = name.to_str().ok_or_else(|| {
+                                WildcardingError::InvalidPath(entry.to_path_buf())
+                            })?;
+                            if re.is_match(as_str) && !exclude.iter().any(|re| re.is_match(as_str))
+                            {
+                                let new_target = resolve_target(&entry, target)?;
+                                matches.push((entry, new_target));
+                            }
                         }
                     }
                 }
-            }
+                matches
+            };
+
+            let concurrency = options.concurrency.max(1);
+            let results: Vec<Result<(), WildcardingError>> = futures::stream::iter(matches)
+                .map(|(file, new_target)| async move {
+                    if let Some(parent) = new_target.parent() {
+                        tokio::fs::create_dir_all(parent).await.map_err(|err| {
+                            WildcardingError::OperationFailed(
+                                file.clone(),
+                                new_target.clone(),
+                                err,
+                            )
+                        })?;
+                    }
+                    op(file, new_target).await
+                })
+                .buffer_unordered(concurrency)
+                .collect()
+                .await;
+
+            results.into_iter().collect::<Result<(), _>>()?;
             Ok(())
         } else {
             Err(WildcardingError::InvalidSource(source.to_path_buf()))
@@ -171,6 +601,137 @@ where
     }
 }
 
+/// Walk `dir` (relative to `root`) and apply `op` to every file found, reproducing the
+/// directory structure under `target` as it goes. Entries whose name matches `exclude` are
+/// skipped, and an excluded directory is not walked.
+fn do_on_dir_recursive<'a, T, Fut>(
+    root: &'a Path,
+    dir: &'a Path,
+    target: &'a Path,
+    op: T,
+    exclude: &'a [Regex],
+) -> std::pin::Pin<Box<dyn Future<Output = Result<(), WildcardingError>> + Send + 'a>>
+where
+    T: Fn(PathBuf, PathBuf) -> Fut + Send + std::marker::Sync + std::marker::Copy + 'a,
+    Fut: Future<Output = Result<(), WildcardingError>> + Send + 'a,
+{
+    Box::pin(async move {
+        let mut reader = tokio::fs::read_dir(dir)
+            .await
+            .map_err(|err| WildcardingError::ReadError(dir.to_path_buf(), err))?;
+
+        while let Ok(Some(entry)) = reader.next_entry().await {
+            let entry = entry.path();
+            let relative = entry
+                .strip_prefix(root)
+                .map_err(|_| WildcardingError::InvalidPath(entry.to_path_buf()))?;
+
+            if !exclude.is_empty() {
+                let name = entry
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| WildcardingError::InvalidPath(entry.to_path_buf()))?;
+                if exclude.iter().any(|re| re.is_match(name)) {
+                    continue;
+                }
+            }
+
+            let entry_target = target.join(relative);
+
+            if entry.is_dir() {
+                tokio::fs::create_dir_all(&entry_target)
+                    .await
+                    .map_err(|err| {
+                        WildcardingError::OperationFailed(
+                            entry.to_path_buf(),
+                            entry_target.clone(),
+                            err,
+                        )
+                    })?;
+                do_on_dir_recursive(root, &entry, target, op, exclude).await?;
+            } else if entry.is_file() {
+                op(entry, entry_target).await?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Collect every file found under `dir`, walking into subdirectories.
+fn list_files_recursive(
+    dir: &Path,
+) -> std::pin::Pin<
+    Box<dyn std::future::Future<Output = Result<Vec<PathBuf>, WildcardingError>> + Send + '_>,
+> {
+    Box::pin(async move {
+        let mut reader = tokio::fs::read_dir(dir)
+            .await
+            .map_err(|err| WildcardingError::ReadError(dir.to_path_buf(), err))?;
+
+        let mut files = Vec::new();
+        while let Ok(Some(entry)) = reader.next_entry().await {
+            let entry = entry.path();
+            if entry.is_dir() {
+                files.extend(list_files_recursive(&entry).await?);
+            } else if entry.is_file() {
+                files.push(entry);
+            }
+        }
+
+        Ok(files)
+    })
+}
+
+/// Remove every file under `dir` that doesn't match `exclude`, then remove directories that end
+/// up empty, walking back up from the leaves. Returns whether `dir` itself ended up empty, so a
+/// directory holding a skipped file (directly or in a subdirectory) is left in place instead of
+/// being removed out from under it.
+fn remove_dir_recursive<'a>(
+    dir: &'a Path,
+    exclude: &'a [Regex],
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<bool, WildcardingError>> + Send + 'a>>
+{
+    Box::pin(async move {
+        let mut reader = tokio::fs::read_dir(dir)
+            .await
+            .map_err(|err| WildcardingError::ReadError(dir.to_path_buf(), err))?;
+
+        let mut is_empty = true;
+
+        while let Ok(Some(entry)) = reader.next_entry().await {
+            let entry = entry.path();
+
+            if !exclude.is_empty() {
+                let name = entry
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| WildcardingError::InvalidPath(entry.to_path_buf()))?;
+                if exclude.iter().any(|re| re.is_match(name)) {
+                    is_empty = false;
+                    continue;
+                }
+            }
+
+            if entry.is_dir() {
+                if remove_dir_recursive(&entry, exclude).await? {
+                    tokio::fs::remove_dir(&entry)
+                        .await
+                        .map_err(|err| WildcardingError::RemoveFailed(entry.to_path_buf(), err))?;
+                } else {
+                    is_empty = false;
+                }
+            } else if entry.is_file() {
+                tokio::fs::remove_file(&entry)
+                    .await
+                    .map_err(|err| WildcardingError::RemoveFailed(entry.to_path_buf(), err))?;
+            }
+        }
+
+        Ok(is_empty)
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -264,6 +825,86 @@ mod test {
         tokio::fs::remove_dir_all(&wd).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn copy_mask_does_not_match_unescaped_dot() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-mask-escaped-dot");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(&source).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1.txt"), "this is txt")
+            .await
+            .unwrap();
+        // without escaping the literal `.`, the mask `file1.*` would also match
+        // `file1Xtxt`, since `.` means "any character" in a regex.
+        tokio::fs::write(source.join("file1Xtxt"), "not a match")
+            .await
+            .unwrap();
+
+        cp(&source.join("file1.*"), &target).await.unwrap();
+
+        assert!(target.join("file1.txt").is_file());
+        assert!(!target.join("file1Xtxt").is_file());
+
+        tokio::fs::remove_dir_all(&wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_mask_is_anchored() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-mask-anchored");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(&source).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1.txt"), "this is txt")
+            .await
+            .unwrap();
+        // without anchoring, the mask `*.txt` would also match as a substring
+        // of `file1.txt.bak`.
+        tokio::fs::write(source.join("file1.txt.bak"), "not a match")
+            .await
+            .unwrap();
+
+        cp(&source.join("*.txt"), &target).await.unwrap();
+
+        assert!(target.join("file1.txt").is_file());
+        assert!(!target.join("file1.txt.bak").is_file());
+
+        tokio::fs::remove_dir_all(&wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_mask_without_star_still_reaches_the_matcher() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-mask-no-star");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(&source).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1.txt"), "this is txt")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("file2.txt"), "this is also txt")
+            .await
+            .unwrap();
+
+        cp(&source.join("file?.txt"), &target).await.unwrap();
+        cp(&source.join("[fg]ile1.txt"), &target).await.unwrap();
+
+        assert!(target.join("file1.txt").is_file());
+        assert!(target.join("file2.txt").is_file());
+
+        tokio::fs::remove_dir_all(&wd).await.unwrap();
+    }
+
     #[tokio::test]
     async fn copy_star() {
         let temp = std::env::temp_dir();
@@ -450,4 +1091,396 @@ mod test {
 
         tokio::fs::remove_dir_all(wd).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn copy_dir_recursive() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-dir-recursive");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(source.join("nested")).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1"), "this is file 1")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("nested").join("file2"), "this is file 2")
+            .await
+            .unwrap();
+
+        cp_recursive(&source, &target).await.unwrap();
+
+        assert!(target.join("file1").is_file());
+        assert!(target.join("nested").join("file2").is_file());
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_dir_recursive_honours_exclude() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-dir-recursive-exclude");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(source.join("nested")).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1.txt"), "this is file 1")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("nested").join("file2.tmp"), "this is file 2")
+            .await
+            .unwrap();
+
+        cp_with_options(
+            &source,
+            &target,
+            &Options {
+                recursive: true,
+                exclude: vec!["*.tmp".to_string()],
+                ..Options::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(target.join("file1.txt").is_file());
+        assert!(!target.join("nested").join("file2.tmp").is_file());
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn move_dir_recursive() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("mv-dir-recursive");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(source.join("nested")).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1"), "this is file 1")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("nested").join("file2"), "this is file 2")
+            .await
+            .unwrap();
+
+        mv_recursive(&source, &target).await.unwrap();
+
+        assert!(!source.is_dir());
+        assert!(target.join("file1").is_file());
+        assert!(target.join("nested").join("file2").is_file());
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn remove_dir_recursive_prunes_empty_directories() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("rm-dir-recursive");
+        let source = wd.join("source");
+
+        tokio::fs::create_dir_all(source.join("nested")).await.unwrap();
+        tokio::fs::write(source.join("file1"), "this is file 1")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("nested").join("file2"), "this is file 2")
+            .await
+            .unwrap();
+
+        rm_recursive(&source).await.unwrap();
+
+        assert!(!source.join("file1").is_file());
+        assert!(!source.join("nested").is_dir());
+        assert!(!source.is_dir());
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn remove_dir_recursive_leaves_excluded_files_and_their_directory_in_place() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("rm-dir-recursive-exclude");
+        let source = wd.join("source");
+
+        tokio::fs::create_dir_all(source.join("nested")).await.unwrap();
+        tokio::fs::write(source.join("file1"), "this is file 1")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("nested").join("keep.keep"), "keep me")
+            .await
+            .unwrap();
+
+        rm_with_options(
+            &source,
+            &Options {
+                recursive: true,
+                exclude: vec!["*.keep".to_string()],
+                ..Options::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(!source.join("file1").is_file());
+        assert!(source.join("nested").join("keep.keep").is_file());
+        assert!(source.is_dir());
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn move_dir_recursive_leaves_excluded_files_and_their_directory_in_place() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("mv-dir-recursive-exclude");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(source.join("nested")).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1"), "this is file 1")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("nested").join("keep.log"), "keep me")
+            .await
+            .unwrap();
+
+        mv_with_options(
+            &source,
+            &target,
+            &Options {
+                recursive: true,
+                exclude: vec!["*.log".to_string()],
+                ..Options::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(target.join("file1").is_file());
+        assert!(!target.join("nested").join("keep.log").is_file());
+        assert!(source.join("nested").join("keep.log").is_file());
+        assert!(source.is_dir());
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_mask_double_star_crosses_directories() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-mask-double-star");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(source.join("nested")).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1.txt"), "this is txt")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("nested").join("file2.txt"), "nested txt")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("nested").join("file1.glob"), "not txt")
+            .await
+            .unwrap();
+
+        cp(&source.join("**.txt"), &target).await.unwrap();
+
+        assert!(target.join("file1.txt").is_file());
+        assert!(target.join("nested").join("file2.txt").is_file());
+        assert!(!target.join("nested").join("file1.glob").is_file());
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_mask_double_star_preserves_relative_path_on_basename_collision() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-mask-double-star-collision");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(source.join("a")).await.unwrap();
+        tokio::fs::create_dir_all(source.join("b")).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("a").join("file.txt"), "from a")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("b").join("file.txt"), "from b")
+            .await
+            .unwrap();
+
+        cp(&source.join("**.txt"), &target).await.unwrap();
+
+        assert_eq!(
+            tokio::fs::read_to_string(target.join("a").join("file.txt"))
+                .await
+                .unwrap(),
+            "from a"
+        );
+        assert_eq!(
+            tokio::fs::read_to_string(target.join("b").join("file.txt"))
+                .await
+                .unwrap(),
+            "from b"
+        );
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_mask_with_bounded_concurrency() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-mask-concurrency");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(&source).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        for i in 0..20 {
+            tokio::fs::write(source.join(format!("file{i}.txt")), "this is txt")
+                .await
+                .unwrap();
+        }
+
+        cp_with_options(
+            &source.join("*.txt"),
+            &target,
+            &Options {
+                concurrency: 2,
+                ..Options::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        for i in 0..20 {
+            assert!(target.join(format!("file{i}.txt")).is_file());
+        }
+
+        tokio::fs::remove_dir_all(&wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_atomic_leaves_no_temp_file_behind() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-atomic");
+        tokio::fs::create_dir_all(&wd).await.unwrap();
+
+        let source = wd.join("source");
+        let target = wd.join("target");
+        tokio::fs::write(&source, "this is source").await.unwrap();
+
+        cp(&source, &target).await.unwrap();
+
+        assert_eq!(
+            tokio::fs::read_to_string(&target).await.unwrap(),
+            "this is source"
+        );
+
+        let mut entries = tokio::fs::read_dir(&wd).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name());
+        }
+        assert_eq!(names.len(), 2);
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-preserve-permissions");
+        tokio::fs::create_dir_all(&wd).await.unwrap();
+
+        let source = wd.join("source");
+        let target = wd.join("target");
+        tokio::fs::write(&source, "this is source").await.unwrap();
+        tokio::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o640))
+            .await
+            .unwrap();
+
+        cp(&source, &target).await.unwrap();
+
+        let source_mode = tokio::fs::metadata(&source).await.unwrap().permissions().mode();
+        let target_mode = tokio::fs::metadata(&target).await.unwrap().permissions().mode();
+        assert_eq!(source_mode & 0o777, target_mode & 0o777);
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_without_preserve_does_not_require_matching_permissions() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-no-preserve");
+        tokio::fs::create_dir_all(&wd).await.unwrap();
+
+        let source = wd.join("source");
+        let target = wd.join("target");
+        tokio::fs::write(&source, "this is source").await.unwrap();
+
+        cp_with_options(
+            &source,
+            &target,
+            &Options {
+                preserve: false,
+                ..Options::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(target.is_file());
+
+        tokio::fs::remove_dir_all(wd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn copy_mask_with_exclude_skips_matching_files() {
+        let temp = std::env::temp_dir();
+        let wd = temp.join("cp-mask-exclude");
+        let source = wd.join("source");
+        let target = wd.join("target");
+
+        tokio::fs::create_dir_all(&source).await.unwrap();
+        tokio::fs::create_dir_all(&target).await.unwrap();
+
+        tokio::fs::write(source.join("file1.txt"), "this is txt")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("file2.txt"), "this is also txt")
+            .await
+            .unwrap();
+        tokio::fs::write(source.join("file1.tmp.txt"), "this is a temp file")
+            .await
+            .unwrap();
+
+        cp_with_options(
+            &source.join("*.txt"),
+            &target,
+            &Options {
+                exclude: vec!["*.tmp.txt".to_string()],
+                ..Options::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(target.join("file1.txt").is_file());
+        assert!(target.join("file2.txt").is_file());
+        assert!(!target.join("file1.tmp.txt").is_file());
+
+        tokio::fs::remove_dir_all(&wd).await.unwrap();
+    }
 }